@@ -14,7 +14,13 @@ pub enum Config {
     /// Build the project in the current working directory, in either release or debug mode, depending on `mode`
     Build { mode: BuildMode },
     /// Run the project in the current working directory, in either release or debug mode, depending on `mode`
-    Run { mode: BuildMode },
+    Run {
+        mode: BuildMode,
+        /// Arguments forwarded to the launched program, after a `--` separator
+        args: Vec<String>,
+    },
+    /// Watch the project's `src/` and rebuild whenever a source file changes
+    Watch { mode: BuildMode },
 }
 
 /// The optimisation level to be used for compilation
@@ -77,7 +83,32 @@ impl Config {
                     }
                 }
                 "run" => {
+                    // Parse the optional mode flag, stopping short of a `--`
+                    // separator which hands the remaining argv to the program.
+                    let mode = match args.peek().map(String::as_str) {
+                        Some("--debug") => {
+                            args.next();
+                            BuildMode::Debug
+                        }
+                        Some("--release") => {
+                            args.next();
+                            BuildMode::Release
+                        }
+                        Some("--") | None => BuildMode::Debug,
+                        Some(_) => return Err(ArgsError::InvalidFlag(args.next().unwrap())),
+                    };
+                    let mut trailing = Vec::new();
+                    if args.peek().map(String::as_str) == Some("--") {
+                        args.next();
+                        trailing.extend(args.by_ref());
+                    }
                     config = Self::Run {
+                        mode,
+                        args: trailing,
+                    }
+                }
+                "watch" => {
+                    config = Self::Watch {
                         mode: match args.next().map(|mode_str| match mode_str.as_str() {
                             "--debug" => ControlFlow::Continue(BuildMode::Debug),
                             "--release" => ControlFlow::Continue(BuildMode::Release),
@@ -19,11 +19,37 @@ pub enum Command {
     Build {
         #[arg(short, long)]
         mode: BuildMode,
+        /// Number of parallel compile jobs (defaults to available parallelism)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Emit a 'compile_commands.json' compilation database
+        #[arg(long)]
+        emit_compile_commands: bool,
+        /// Echo every compiler command before it runs
+        #[arg(short, long)]
+        verbose: bool,
     },
     /// Build and run a bake project in the CWD
     Run {
         #[arg(short, long)]
         mode: BuildMode,
+        /// Number of parallel compile jobs (defaults to available parallelism)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Emit a 'compile_commands.json' compilation database
+        #[arg(long)]
+        emit_compile_commands: bool,
+        /// Echo every compiler command before it runs
+        #[arg(short, long)]
+        verbose: bool,
+        /// Arguments forwarded to the program after a `--` separator
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Watch 'src/' and rebuild whenever a source file changes
+    Watch {
+        #[arg(short, long)]
+        mode: BuildMode,
     },
 }
 
@@ -17,7 +17,19 @@ fn run() -> anyhow::Result<()> {
     let config = Config::parse();
     match config.command {
         Command::New { name } => project::new_project(&name),
-        Command::Build { mode } => project::build_project(mode),
-        Command::Run { mode } => project::run_project(mode),
+        Command::Build {
+            mode,
+            jobs,
+            emit_compile_commands,
+            verbose,
+        } => project::build_project(mode, jobs, emit_compile_commands, verbose),
+        Command::Run {
+            mode,
+            jobs,
+            emit_compile_commands,
+            verbose,
+            args,
+        } => project::run_project(mode, jobs, &args, emit_compile_commands, verbose),
+        Command::Watch { mode } => project::watch_project(mode),
     }
 }
@@ -2,16 +2,21 @@ use std::{
     env,
     ffi::OsStr,
     fs,
-    path::Path,
+    path::{Path, PathBuf},
     process::{self, Command},
     str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
 };
 
-use anyhow::{bail, Context};
+use anyhow::{anyhow, bail, Context};
 use lazy_static::lazy_static;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::BuildMode;
 
@@ -50,6 +55,59 @@ pub fn new_project(name: &str) -> anyhow::Result<()> {
 #[derive(Deserialize)]
 pub struct ProjConfig {
     pub name: String,
+    /// The language standard, e.g. `c11` or `c++17`, passed as `-std=<std>`.
+    #[serde(default)]
+    pub std: Option<String>,
+    /// Extra flags passed when compiling C translation units.
+    #[serde(default)]
+    pub cflags: Vec<String>,
+    /// Extra flags passed when compiling C++ translation units.
+    #[serde(default)]
+    pub cxxflags: Vec<String>,
+    /// Header search paths, each emitted as `-I<dir>`.
+    #[serde(default)]
+    pub include_dirs: Vec<String>,
+    /// Preprocessor definitions, each emitted as `-D<define>`.
+    #[serde(default)]
+    pub defines: Vec<String>,
+    /// Libraries to link, each emitted as `-l<lib>`.
+    #[serde(default)]
+    pub libs: Vec<String>,
+    /// Extra flags passed to the final link step.
+    #[serde(default)]
+    pub ldflags: Vec<String>,
+    /// Per-profile overrides keyed by `[profile.debug]` / `[profile.release]`.
+    #[serde(default)]
+    pub profile: Profiles,
+}
+
+/// The `[profile.*]` tables of a manifest.
+#[derive(Deserialize, Default)]
+pub struct Profiles {
+    #[serde(default)]
+    pub debug: Option<Profile>,
+    #[serde(default)]
+    pub release: Option<Profile>,
+}
+
+impl Profiles {
+    /// The profile overriding the given build mode, if one is configured.
+    fn for_mode(&self, mode: BuildMode) -> Option<&Profile> {
+        match mode {
+            BuildMode::Debug => self.debug.as_ref(),
+            BuildMode::Release => self.release.as_ref(),
+        }
+    }
+}
+
+/// Overrides applied for a single build mode.
+#[derive(Deserialize, Default)]
+pub struct Profile {
+    /// Optimisation/codegen flags replacing [`BuildMode::to_flag`] (e.g.
+    /// `["-O2", "-g"]` or `["-fsanitize=address"]`). Applied to both the
+    /// compile and link steps.
+    #[serde(default)]
+    pub flags: Vec<String>,
 }
 
 static CC: Lazy<String> = Lazy::new(|| env::var("CC").unwrap_or_else(|_| "cc".to_string()));
@@ -80,48 +138,358 @@ impl FromStr for Lang {
     }
 }
 
-pub fn build_project(mode: BuildMode) -> anyhow::Result<()> {
+/// Resolve the requested number of compile jobs, defaulting to the available
+/// parallelism when the user did not pass `-j/--jobs`.
+fn resolve_jobs(jobs: Option<usize>) -> usize {
+    jobs.or_else(|| thread::available_parallelism().ok().map(Into::into))
+        .unwrap_or(1)
+        .max(1)
+}
+
+pub fn build_project(
+    mode: BuildMode,
+    jobs: Option<usize>,
+    emit_compile_commands: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
     let cwd = env::current_dir().context("Failed to get current dir")?;
-    let config: ProjConfig = {
-        let config_str =
-            fs::read_to_string(cwd.join("bake.toml")).context("Failed to read 'bake.toml'")?;
-        toml::from_str(&config_str).context("Failed to parse 'bake.toml'")?
-    };
-    build_project_inner(&config, mode, cwd)
+    let config = read_config(&cwd)?;
+    build_project_inner(
+        &config,
+        mode,
+        cwd,
+        resolve_jobs(jobs),
+        emit_compile_commands,
+        verbose,
+    )
 }
 
-pub fn run_project(mode: BuildMode) -> anyhow::Result<()> {
+pub fn run_project(
+    mode: BuildMode,
+    jobs: Option<usize>,
+    args: &[String],
+    emit_compile_commands: bool,
+    verbose: bool,
+) -> anyhow::Result<()> {
     let cwd = env::current_dir().context("Failed to get current dir")?;
-    let config: ProjConfig = {
-        let config_str =
-            fs::read_to_string(cwd.join("bake.toml")).context("Failed to read 'bake.toml'")?;
-        toml::from_str(&config_str).context("Failed to parse 'bake.toml'")?
-    };
-    build_project_inner(&config, mode, &cwd)?;
-    println!(
-        "    \x1b[1;32mRunning\x1b[0m {}",
-        cwd.join("bin")
-            .join(mode.to_string())
-            .join(&config.name)
-            .display()
-    );
-    process::Command::new(cwd.join("bin").join(mode.to_string()).join(&config.name))
+    let config = read_config(&cwd)?;
+    build_project_inner(
+        &config,
+        mode,
+        &cwd,
+        resolve_jobs(jobs),
+        emit_compile_commands,
+        verbose,
+    )?;
+    let executable = cwd.join("bin").join(mode.to_string()).join(&config.name);
+    println!("    \x1b[1;32mRunning\x1b[0m {}", executable.display());
+    let status = process::Command::new(&executable)
+        .args(args)
         .spawn()
         .with_context(|| format!("Failed to run '{}'", &config.name))?
         .wait()?;
+    // Behave transparently in pipelines: forward the child's exit code, and
+    // report termination-by-signal as a conventional `128 + signal` exit.
+    process::exit(exit_code(status));
+}
+
+/// Translate a child's [`process::ExitStatus`] into a process exit code,
+/// mapping termination-by-signal onto a nonzero `128 + signal` value.
+fn exit_code(status: process::ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+/// Render an argv as a single, copy-pasteable command line, quoting any
+/// argument that contains whitespace.
+fn shell_join(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            if arg.contains(char::is_whitespace) {
+                format!("'{arg}'")
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Describe how a process terminated, distinguishing a nonzero exit code from
+/// termination by signal for the `Caused By:` line of the error chain.
+fn describe_status(status: process::ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exited with code {code}");
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("terminated by signal {signal}");
+        }
+    }
+    "terminated abnormally".to_string()
+}
+
+/// Run one compiler/linker step, echoing the command when `verbose`. On failure
+/// the returned error carries the exact resolved command line and how the
+/// process terminated, rendered through `anyhow`'s `Caused By:` chain.
+fn run_step(argv: &[String], verbose: bool) -> anyhow::Result<()> {
+    if verbose {
+        println!("    \x1b[1;34mRunning\x1b[0m {}", shell_join(argv));
+    }
+    let status = Command::new(&argv[0])
+        .args(&argv[1..])
+        .status()
+        .with_context(|| format!("Failed to spawn '{}'", argv[0]))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(describe_status(status)))
+            .with_context(|| format!("Command failed: {}", shell_join(argv)))
+    }
+}
+
+/// A single translation unit queued for compilation.
+struct CompileUnit {
+    source_path: PathBuf,
+    object_path: PathBuf,
+    lang: Lang,
+}
+
+/// An entry in a Clang JSON Compilation Database (`compile_commands.json`).
+#[derive(Serialize)]
+struct CompileCommand {
+    directory: String,
+    file: String,
+    arguments: Vec<String>,
+    output: String,
+}
+
+/// Resolved, per-build compile settings spliced into every compiler invocation.
+/// Built once from the manifest and active profile, then shared across workers.
+struct CompileFlags {
+    opt: Vec<String>,
+    std: Option<String>,
+    include_dirs: Vec<String>,
+    defines: Vec<String>,
+    cflags: Vec<String>,
+    cxxflags: Vec<String>,
+}
+
+impl CompileFlags {
+    /// Resolve the compile settings for `mode`, letting an active profile's
+    /// `flags` stand in for [`BuildMode::to_flag`].
+    fn resolve(config: &ProjConfig, mode: BuildMode) -> Self {
+        let opt = match config.profile.for_mode(mode) {
+            Some(profile) if !profile.flags.is_empty() => profile.flags.clone(),
+            _ => vec![mode.to_flag().to_string()],
+        };
+        Self {
+            opt,
+            std: config.std.clone(),
+            include_dirs: config.include_dirs.clone(),
+            defines: config.defines.clone(),
+            cflags: config.cflags.clone(),
+            cxxflags: config.cxxflags.clone(),
+        }
+    }
+
+    /// The language-specific user flags for a translation unit.
+    fn lang_flags(&self, lang: &Lang) -> &[String] {
+        match lang {
+            Lang::C => &self.cflags,
+            Lang::Cpp => &self.cxxflags,
+        }
+    }
+}
+
+impl CompileUnit {
+    /// The full compiler argv for this unit, starting with the compiler itself.
+    /// This is the single source of truth shared by the actual `cc -c`
+    /// invocation and the emitted `compile_commands.json` entry.
+    fn compiler_arguments(&self, flags: &CompileFlags) -> Vec<String> {
+        let mut argv = vec![self.lang.get_compiler().to_string()];
+        argv.extend(flags.opt.iter().cloned());
+        argv.push("-g".to_string());
+        if let Some(std) = &flags.std {
+            argv.push(format!("-std={std}"));
+        }
+        argv.extend(flags.include_dirs.iter().map(|dir| format!("-I{dir}")));
+        argv.extend(flags.defines.iter().map(|def| format!("-D{def}")));
+        argv.extend(flags.lang_flags(&self.lang).iter().cloned());
+        argv.extend([
+            "-c".to_string(),
+            self.source_path.to_string_lossy().to_string(),
+            "-o".to_string(),
+            self.object_path.to_string_lossy().to_string(),
+        ]);
+        argv
+    }
+
+    /// The compilation-database entry describing this unit.
+    fn compile_command(&self, directory: &Path, flags: &CompileFlags) -> CompileCommand {
+        CompileCommand {
+            directory: directory.to_string_lossy().to_string(),
+            file: self.source_path.to_string_lossy().to_string(),
+            arguments: self.compiler_arguments(flags),
+            output: self.object_path.to_string_lossy().to_string(),
+        }
+    }
+}
+
+/// Compile one translation unit, skipping it when the object is already up to
+/// date. Returns `Ok(())` for both a successful compile and a skipped file.
+fn compile_unit(
+    unit: &CompileUnit,
+    flags: &CompileFlags,
+    verbose: bool,
+    stdout: &Mutex<()>,
+) -> anyhow::Result<()> {
+    let source_metadata = unit
+        .source_path
+        .metadata()
+        .with_context(|| format!("Failed to read metadata of '{}'", unit.source_path.display()))?;
+    if unit.object_path.exists()
+        && source_metadata.modified()? <= unit.object_path.metadata()?.modified()?
+    {
+        return Ok(());
+    }
+    let argv = unit.compiler_arguments(flags);
+    run_step(&argv, verbose)
+        .with_context(|| format!("Failed to compile file '{}'", unit.source_path.display()))?;
+    let _guard = stdout.lock().unwrap();
+    println!(
+        "    \x1b[1;32mCompiled\x1b[0m {}",
+        unit.source_path.display()
+    );
     Ok(())
 }
 
+/// Read and parse `bake.toml` from `cwd`.
+fn read_config(cwd: &Path) -> anyhow::Result<ProjConfig> {
+    let config_str =
+        fs::read_to_string(cwd.join("bake.toml")).context("Failed to read 'bake.toml'")?;
+    toml::from_str(&config_str).context("Failed to parse 'bake.toml'")
+}
+
+/// Watch `src/` recursively and rebuild on every `.c`/`.cc`/`.cpp` change.
+///
+/// A burst of editor writes is coalesced into a single rebuild by waiting for a
+/// ~200ms quiet period after the last filesystem event of any kind.
+pub fn watch_project(mode: BuildMode) -> anyhow::Result<()> {
+    use std::{sync::mpsc, time::Duration};
+
+    use notify::{event::ModifyKind, EventKind, RecursiveMode, Watcher};
+
+    const DEBOUNCE: Duration = Duration::from_millis(200);
+
+    let cwd = env::current_dir().context("Failed to get current dir")?;
+    let src_dir = cwd.join("src");
+    let jobs = resolve_jobs(None);
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res| {
+        // A send failure just means we're shutting down; nothing to do.
+        let _ = tx.send(res);
+    })
+    .context("Failed to create filesystem watcher")?;
+    watcher
+        .watch(&src_dir, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch '{}'", src_dir.display()))?;
+
+    println!(
+        "    \x1b[1;32mWatching\x1b[0m {} (Ctrl-C to stop)",
+        src_dir.display()
+    );
+    rebuild(&cwd, mode, jobs);
+
+    loop {
+        // Block until something happens, then drain the burst until the tree
+        // has been quiet for `DEBOUNCE`, so a save-all coalesces into one build.
+        let event = rx.recv().context("Filesystem watcher disconnected")?;
+        let mut dirty = is_source_event(&event);
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(event) => dirty |= is_source_event(&event),
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    bail!("Filesystem watcher disconnected");
+                }
+            }
+        }
+        if dirty {
+            rebuild(&cwd, mode, jobs);
+        }
+    }
+
+    /// Does this event touch a C/C++ source file being created, written, or removed?
+    fn is_source_event(event: &notify::Result<notify::Event>) -> bool {
+        let Ok(event) = event else {
+            return false;
+        };
+        if !matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Remove(_)
+                | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+        ) {
+            return false;
+        }
+        event.paths.iter().any(|path| {
+            path.extension()
+                .and_then(OsStr::to_str)
+                .and_then(|ext| ext.parse::<Lang>().ok())
+                .is_some()
+        })
+    }
+}
+
+/// Run a single rebuild for the watch loop, reporting the outcome as a colored
+/// status line and emitting a desktop notification on failure.
+fn rebuild(cwd: &Path, mode: BuildMode, jobs: usize) {
+    let result = read_config(cwd)
+        .and_then(|config| build_project_inner(&config, mode, cwd, jobs, true, false));
+    match result {
+        Ok(()) => println!("    \x1b[1;32mFinished\x1b[0m {mode} build"),
+        Err(err) => {
+            eprintln!("\x1b[1;31mError:\x1b[0m {err}");
+            err.chain()
+                .skip(1)
+                .for_each(|cause| eprintln!("\x1b[1;90mCaused By:\x1b[0m {cause}"));
+            let _ = notify_rust::Notification::new()
+                .summary("bake: build failed")
+                .body(&err.to_string())
+                .show();
+        }
+    }
+}
+
 pub fn build_project_inner(
     config: &ProjConfig,
     mode: BuildMode,
     path: impl AsRef<Path>,
+    jobs: usize,
+    emit_compile_commands: bool,
+    verbose: bool,
 ) -> anyhow::Result<()> {
-    let opt_level = mode.to_flag();
+    let flags = CompileFlags::resolve(config, mode);
+    let project_dir = path.as_ref();
     let src_dir = path.as_ref().join("src");
     let bin_dir = path.as_ref().join("bin").join(mode.to_string());
     fs::create_dir_all(&bin_dir)
         .with_context(|| format!("Failed to create 'bin/' in '{}'", path.as_ref().display()))?;
+
+    let mut units = Vec::new();
     for source in fs::read_dir(&src_dir).context("Failed to read 'src/'")? {
         let source = source.with_context(|| {
             format!(
@@ -151,27 +519,54 @@ pub fn build_project_inner(
                 .to_string();
             bin_dir.join(file_name + ".o")
         };
-        if !object_path.exists()
-            || source_metadata.modified()? > object_path.metadata()?.modified()?
-        {
-            if Command::new(lang.get_compiler())
-                .args([
-                    opt_level,
-                    "-g",
-                    "-c",
-                    source_path.to_str().unwrap(),
-                    "-o",
-                    object_path.to_str().unwrap(),
-                ])
-                .status()?
-                .success()
-            {
-                println!("    \x1b[1;32mCompiled\x1b[0m {}", source_path.display());
-            } else {
-                bail!("Failed to compile file '{}'", source_path.display());
-            }
+        units.push(CompileUnit {
+            source_path,
+            object_path,
+            lang,
+        });
+    }
+
+    // Dispatch the per-file `cc -c` invocations across a pool of workers. Each
+    // worker pops the next unit, evaluates staleness itself (so unchanged files
+    // are skipped), and bails the whole build on the first compile error.
+    let next = AtomicUsize::new(0);
+    let first_error: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+    let stdout = Mutex::new(());
+    let worker_count = jobs.min(units.len()).max(1);
+    thread::scope(|scope| {
+        for _ in 0..worker_count {
+            scope.spawn(|| loop {
+                if first_error.lock().unwrap().is_some() {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(unit) = units.get(index) else {
+                    break;
+                };
+                if let Err(err) = compile_unit(unit, &flags, verbose, &stdout) {
+                    first_error.lock().unwrap().get_or_insert(err);
+                    break;
+                }
+            });
         }
+    });
+    if let Some(err) = first_error.into_inner().unwrap() {
+        return Err(err);
+    }
+
+    // Regenerate the compilation database so clangd/clang-tidy see the exact
+    // flags `bake` used for every translation unit.
+    if emit_compile_commands {
+        let commands = units
+            .iter()
+            .map(|unit| unit.compile_command(project_dir, &flags))
+            .collect::<Vec<_>>();
+        let json = serde_json::to_string_pretty(&commands)
+            .context("Failed to serialize 'compile_commands.json'")?;
+        fs::write(project_dir.join("compile_commands.json"), json)
+            .context("Failed to write 'compile_commands.json'")?;
     }
+
     let mut object_files = Vec::new();
     for source in
         fs::read_dir(&bin_dir).with_context(|| format!("Failed to read '{}'", bin_dir.display()))?
@@ -199,17 +594,17 @@ pub fn build_project_inner(
         }
         object_files.push(source_path.to_string_lossy().to_string());
     }
-    if !Command::new(&*CC)
-        .args(object_files.iter().map(AsRef::as_ref).chain([
-            opt_level,
-            "-o",
-            bin_dir.join(&config.name).to_str().unwrap(),
-        ]))
-        .status()?
-        .success()
-    {
-        bail!("Failed to link executable")
-    }
+    let mut link_args = object_files;
+    link_args.extend(flags.opt.iter().cloned());
+    link_args.extend(config.ldflags.iter().cloned());
+    link_args.extend(config.libs.iter().map(|lib| format!("-l{lib}")));
+    link_args.extend([
+        "-o".to_string(),
+        bin_dir.join(&config.name).to_string_lossy().to_string(),
+    ]);
+    // Prepend the linker driver so the logged command line is complete.
+    link_args.insert(0, CC.clone());
+    run_step(&link_args, verbose).context("Failed to link executable")?;
     println!("    \x1b[1;32mCompiled\x1b[0m '{}'", config.name);
     Ok(())
 }